@@ -2,24 +2,56 @@ use borsh::de::BorshDeserialize;
 use clap::{ArgMatches, Args, Parser, Subcommand};
 use solana_clap_v3_utils::keypair::pubkey_from_path;
 use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_account_decoder::UiAccountEncoding;
 use solana_instruction::Instruction;
 use solana_sdk::account::ReadableAccount;
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{read_keypair_file, Signer};
 use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::stake::state::{Authorized, Lockup};
 use solana_sdk::system_instruction;
 use solana_sdk::transaction::Transaction;
+use solana_program::borsh1::get_instance_packed_len;
 use spl_associated_token_account_client::address::get_associated_token_address;
-use spl_stake_pool::state::{StakePool, ValidatorList, ValidatorStakeInfo};
+use spl_stake_pool::state::{Fee, StakePool, ValidatorList, ValidatorStakeInfo};
 use std::num::NonZeroU32;
+use std::str::FromStr;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct CommandArgs {
-    /// The pool to use
+    /// The pool to use (not required when creating a new pool)
     #[clap(short, long = "pool", value_parser = |p: &str| parse_address(p, "pool-address"))]
-    pub pool_address: Pubkey,
+    pub pool_address: Option<Pubkey>,
+
+    /// JSON RPC URL for the cluster to target
+    #[clap(long = "url", default_value = "http://localhost:8899")]
+    pub url: String,
+
+    /// Keypair file used as the depositor / staking authority and to sign administrative
+    /// operations. Defaults to the Solana CLI's configured keypair (~/.config/solana/id.json)
+    #[clap(long = "depositor")]
+    pub depositor: Option<String>,
+
+    /// Keypair file used to pay transaction fees. Defaults to the same keypair as
+    /// --depositor, but can be set separately so fees don't draw down the depositor's balance
+    #[clap(long = "fee-payer")]
+    pub fee_payer: Option<String>,
+
+    /// Bank commitment level to use for RPC requests
+    #[clap(long = "commitment", default_value = "confirmed")]
+    pub commitment: String,
+
+    /// Simulate every transaction instead of sending it
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Skip the pool update that normally runs before every command
+    #[clap(long = "no-update")]
+    pub no_update: bool,
 
     #[clap(subcommand)]
     pub command: Command,
@@ -27,9 +59,93 @@ struct CommandArgs {
 
 #[derive(Clone, Debug, Subcommand)]
 pub enum Command {
+    CreatePool(CreatePoolCommand),
+    AddValidator(AddValidatorCommand),
+    RemoveValidator(RemoveValidatorCommand),
+    SetManager(SetManagerCommand),
+    SetFee(SetFeeCommand),
+    SetStaker(SetStakerCommand),
     DepositSol(DepositCommand),
+    DepositStake(DepositStakeCommand),
+    WithdrawSol(WithdrawSolCommand),
+    WithdrawStake(WithdrawStakeCommand),
     IncreaseValidatorStake(IncreaseCommand),
     DecreaseValidatorStake(DecreaseCommand),
+    /// List every stake pool owned by the stake pool program
+    ListPools,
+    /// List every stake account the pool's withdraw authority controls on-chain
+    ListValidatorStakeAccounts,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct SetManagerCommand {
+    /// New manager for the pool
+    #[clap(short = 'm', long = "new-manager", value_parser = |p: &str| parse_address(p, "new-manager"))]
+    pub new_manager: Pubkey,
+
+    /// New manager fee receiver token account
+    #[clap(short = 'f', long = "new-fee-receiver", value_parser = |p: &str| parse_address(p, "new-fee-receiver"))]
+    pub new_fee_receiver: Pubkey,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct SetFeeCommand {
+    /// Which fee to update
+    #[clap(short = 't', long = "fee-type", value_enum)]
+    pub fee_type: SetFeeType,
+
+    /// New fee numerator
+    #[clap(short = 'n', long = "fee-numerator")]
+    pub fee_numerator: u64,
+
+    /// New fee denominator
+    #[clap(short = 'd', long = "fee-denominator")]
+    pub fee_denominator: u64,
+}
+
+#[derive(Clone, Debug, clap::ValueEnum)]
+pub enum SetFeeType {
+    Epoch,
+    StakeWithdrawal,
+    SolWithdrawal,
+    StakeDeposit,
+    SolDeposit,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct SetStakerCommand {
+    /// New staker for the pool
+    #[clap(short, long = "new-staker", value_parser = |p: &str| parse_address(p, "new-staker"))]
+    pub new_staker: Pubkey,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct CreatePoolCommand {
+    /// Maximum number of validators the pool can support
+    #[clap(short = 'm', long = "max-validators")]
+    pub max_validators: u32,
+
+    /// Epoch fee numerator
+    #[clap(short = 'n', long = "fee-numerator")]
+    pub fee_numerator: u64,
+
+    /// Epoch fee denominator
+    #[clap(short = 'd', long = "fee-denominator")]
+    pub fee_denominator: u64,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct AddValidatorCommand {
+    /// Vote account for the validator to add to the pool
+    #[clap(short, long = "vote-account", value_parser = |p: &str| parse_address(p, "vote-account"))]
+    pub vote_acount: Pubkey,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct RemoveValidatorCommand {
+    /// Vote account for the validator to remove from the pool
+    #[clap(short, long = "vote-account", value_parser = |p: &str| parse_address(p, "vote-account"))]
+    pub vote_acount: Pubkey,
 }
 
 #[derive(Clone, Debug, Args)]
@@ -39,6 +155,35 @@ pub struct DepositCommand {
     pub amount: f64,
 }
 
+#[derive(Clone, Debug, Args)]
+pub struct DepositStakeCommand {
+    /// The already-activated stake account to deposit into the pool
+    #[clap(short, long = "stake-account", value_parser = |p: &str| parse_address(p, "stake-account"))]
+    pub stake_account: Pubkey,
+
+    /// Vote account the stake account is delegated to
+    #[clap(short, long = "vote-account", value_parser = |p: &str| parse_address(p, "vote-account"))]
+    pub vote_acount: Pubkey,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct WithdrawSolCommand {
+    /// The amount in SOL to withdraw
+    #[clap(short, long = "amount")]
+    pub amount: f64,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct WithdrawStakeCommand {
+    /// Vote account for the validator to withdraw stake from
+    #[clap(short, long = "vote-account", value_parser = |p: &str| parse_address(p, "vote-account"))]
+    pub vote_acount: Pubkey,
+
+    /// Amount in SOL to withdraw as a new stake account
+    #[clap(short, long = "amount")]
+    pub amount: f64,
+}
+
 #[derive(Clone, Debug, Args)]
 pub struct IncreaseCommand {
     /// Vote account for the validator to increase stake to
@@ -71,38 +216,91 @@ struct Data {
     client: RpcClient,
     stake_pool_pubkey: Pubkey,
     payer_keypair: Keypair,
+    fee_payer_keypair: Keypair,
+    dry_run: bool,
+}
+
+fn parse_commitment(commitment: &str) -> CommitmentConfig {
+    CommitmentConfig::from_str(commitment)
+        .unwrap_or_else(|_| panic!("Invalid commitment level: {}", commitment))
+}
+
+fn resolve_keypair_path(path: Option<String>) -> String {
+    match path {
+        Some(path) => path,
+        None => {
+            let mut home_dir = dirs::home_dir().unwrap();
+            home_dir.push(".config/solana/id.json");
+            home_dir.to_str().unwrap().to_string()
+        }
+    }
 }
 
 fn main() {
     let args = CommandArgs::parse();
-    // Set up Solana RPC client to talk to Localnet
-    let client = RpcClient::new_with_commitment(
-        "http://localhost:8899".to_string(),
-        CommitmentConfig::confirmed(),
-    );
+    let commitment = parse_commitment(&args.commitment);
+    let client = RpcClient::new_with_commitment(args.url.clone(), commitment);
 
-    let mut home_dir = dirs::home_dir().unwrap();
-    home_dir.push(".config/solana/id.json");
-    let payer_keypair_path = home_dir.to_str().unwrap().to_string();
+    let payer_keypair_path = resolve_keypair_path(args.depositor.clone());
     let payer_keypair = read_keypair_file(payer_keypair_path).unwrap();
     println!("Stake from: {:?}", payer_keypair.pubkey());
     let payer_account = client.get_account(&payer_keypair.pubkey()).unwrap();
     let balance = payer_account.lamports();
     println!("Current available balance: {}", balance);
 
-    let stake_pool_pubkey = args.pool_address;
+    let fee_payer_keypair = match &args.fee_payer {
+        Some(path) => read_keypair_file(path).unwrap(),
+        None => payer_keypair.insecure_clone(),
+    };
+    if fee_payer_keypair.pubkey() != payer_keypair.pubkey() {
+        println!("Paying fees from: {:?}", fee_payer_keypair.pubkey());
+    }
+
+    if let Command::CreatePool(cmd) = &args.command {
+        create_pool(&client, &payer_keypair, &fee_payer_keypair, cmd, args.dry_run);
+        return;
+    }
+    if let Command::ListPools = &args.command {
+        list_pools(&client);
+        return;
+    }
+
+    let stake_pool_pubkey = args
+        .pool_address
+        .expect("--pool is required for this command");
     let data = Data {
         client,
         stake_pool_pubkey,
         payer_keypair,
+        fee_payer_keypair,
+        dry_run: args.dry_run,
     };
 
     print_stake_pool_related_addresses(&data);
     print_stake_pool_financials(&data);
-    update_stake_pool(&data);
+    if args.no_update {
+        println!("Skipping pool update (--no-update)");
+    } else {
+        update_stake_pool(&data);
+    }
 
     match args.command {
+        Command::CreatePool(_) => unreachable!("handled above"),
+        Command::AddValidator(args) => add_validator(&data, &args.vote_acount),
+        Command::RemoveValidator(args) => remove_validator(&data, &args.vote_acount),
+        Command::SetManager(args) => {
+            set_manager(&data, &args.new_manager, &args.new_fee_receiver)
+        }
+        Command::SetFee(args) => set_fee(&data, &args.fee_type, args.fee_numerator, args.fee_denominator),
+        Command::SetStaker(args) => set_staker(&data, &args.new_staker),
+        Command::ListPools => unreachable!("handled above"),
+        Command::ListValidatorStakeAccounts => list_validator_stake_accounts(&data),
         Command::DepositSol(args) => deposit_sol(&data, args.amount),
+        Command::DepositStake(args) => {
+            deposit_stake(&data, &args.stake_account, &args.vote_acount)
+        }
+        Command::WithdrawSol(args) => withdraw_sol(&data, args.amount),
+        Command::WithdrawStake(args) => withdraw_stake(&data, args.amount, &args.vote_acount),
         Command::IncreaseValidatorStake(args) => {
             increase_validator_stake_with_vote(&data, args.amount, &args.vote_acount)
         }
@@ -154,6 +352,7 @@ fn send_instructions(
     fee_payer: &Pubkey,
     signers: &[&Keypair],
     wait: bool,
+    dry_run: bool,
 ) {
     let recent_blockhash = client
         .get_latest_blockhash_with_commitment(
@@ -167,6 +366,11 @@ fn send_instructions(
         &recent_blockhash,
     );
     let transaction = Transaction::new(signers, message, recent_blockhash);
+    if dry_run {
+        let result = client.simulate_transaction(&transaction).unwrap();
+        println!("Dry run simulation result: {:?}", result.value);
+        return;
+    }
     if wait {
         client
             .send_and_confirm_transaction_with_spinner(&transaction)
@@ -184,6 +388,434 @@ fn get_validator_list(client: &RpcClient, validator_list_pubkey: &Pubkey) -> Val
     ValidatorList::deserialize(&mut validator_list_data).unwrap()
 }
 
+fn create_pool(
+    client: &RpcClient,
+    payer_keypair: &Keypair,
+    fee_payer_keypair: &Keypair,
+    cmd: &CreatePoolCommand,
+    dry_run: bool,
+) {
+    let stake_pool_keypair = Keypair::new();
+    let validator_list_keypair = Keypair::new();
+    let pool_mint_keypair = Keypair::new();
+    let manager_fee_account_keypair = Keypair::new();
+
+    let withdraw_authority = spl_stake_pool::find_withdraw_authority_program_address(
+        &spl_stake_pool::id(),
+        &stake_pool_keypair.pubkey(),
+    )
+    .0;
+
+    let validator_list_len = get_instance_packed_len(&ValidatorList::new_with_max_validators(
+        cmd.max_validators,
+    ))
+    .unwrap();
+    let stake_pool_len = get_instance_packed_len(&StakePool::default()).unwrap();
+
+    let stake_pool_rent = client
+        .get_minimum_balance_for_rent_exemption(stake_pool_len)
+        .unwrap();
+    let validator_list_rent = client
+        .get_minimum_balance_for_rent_exemption(validator_list_len)
+        .unwrap();
+    let mint_rent = client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .unwrap();
+    let pool_account_rent = client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+        .unwrap();
+    let reserve_stake_rent = client
+        .get_minimum_balance_for_rent_exemption(solana_sdk::stake::state::StakeStateV2::size_of())
+        .unwrap();
+
+    let reserve_stake_keypair = Keypair::new();
+
+    let instructions = vec![
+        system_instruction::create_account(
+            &payer_keypair.pubkey(),
+            &stake_pool_keypair.pubkey(),
+            stake_pool_rent,
+            stake_pool_len as u64,
+            &spl_stake_pool::id(),
+        ),
+        system_instruction::create_account(
+            &payer_keypair.pubkey(),
+            &validator_list_keypair.pubkey(),
+            validator_list_rent,
+            validator_list_len as u64,
+            &spl_stake_pool::id(),
+        ),
+        system_instruction::create_account(
+            &payer_keypair.pubkey(),
+            &pool_mint_keypair.pubkey(),
+            mint_rent,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &pool_mint_keypair.pubkey(),
+            &withdraw_authority,
+            None,
+            9,
+        )
+        .unwrap(),
+        system_instruction::create_account(
+            &payer_keypair.pubkey(),
+            &manager_fee_account_keypair.pubkey(),
+            pool_account_rent,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &manager_fee_account_keypair.pubkey(),
+            &pool_mint_keypair.pubkey(),
+            &payer_keypair.pubkey(),
+        )
+        .unwrap(),
+        system_instruction::create_account(
+            &payer_keypair.pubkey(),
+            &reserve_stake_keypair.pubkey(),
+            reserve_stake_rent,
+            solana_sdk::stake::state::StakeStateV2::size_of() as u64,
+            &solana_sdk::stake::program::id(),
+        ),
+        solana_sdk::stake::instruction::initialize(
+            &reserve_stake_keypair.pubkey(),
+            &Authorized {
+                staker: withdraw_authority,
+                withdrawer: withdraw_authority,
+            },
+            &Lockup::default(),
+        ),
+        spl_stake_pool::instruction::initialize(
+            &spl_stake_pool::id(),
+            &stake_pool_keypair.pubkey(),
+            &payer_keypair.pubkey(),
+            &payer_keypair.pubkey(),
+            &withdraw_authority,
+            &validator_list_keypair.pubkey(),
+            &reserve_stake_keypair.pubkey(),
+            &pool_mint_keypair.pubkey(),
+            &manager_fee_account_keypair.pubkey(),
+            &spl_token::id(),
+            None,
+            Fee {
+                numerator: cmd.fee_numerator,
+                denominator: cmd.fee_denominator,
+            },
+            Fee::default(),
+            Fee::default(),
+            0,
+            cmd.max_validators,
+        ),
+    ];
+
+    let signers = vec![
+        payer_keypair,
+        fee_payer_keypair,
+        &stake_pool_keypair,
+        &validator_list_keypair,
+        &pool_mint_keypair,
+        &manager_fee_account_keypair,
+        &reserve_stake_keypair,
+    ];
+    send_instructions(
+        client,
+        &instructions,
+        &fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        dry_run,
+    );
+
+    if !dry_run {
+        println!("\n==========================================");
+        println!("Stake Pool Created");
+        println!("==========================================");
+        println!("Stake Pool Pubkey: {}", stake_pool_keypair.pubkey());
+        println!("Use it with --pool {} for subsequent commands", stake_pool_keypair.pubkey());
+    }
+}
+
+fn add_validator(data: &Data, vote_account: &Pubkey) {
+    let stake_pool = get_stake_pool(data);
+    let validator_stake_address = spl_stake_pool::find_stake_program_address(
+        &spl_stake_pool::id(),
+        vote_account,
+        &data.stake_pool_pubkey,
+        None,
+    )
+    .0;
+    let withdraw_authority = spl_stake_pool::find_withdraw_authority_program_address(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+    )
+    .0;
+
+    let add_validator_instruction = spl_stake_pool::instruction::add_validator_to_pool(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &data.payer_keypair.pubkey(),
+        &stake_pool.reserve_stake,
+        &withdraw_authority,
+        &stake_pool.validator_list,
+        &validator_stake_address,
+        vote_account,
+        None,
+    );
+
+    let instructions = vec![add_validator_instruction];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    if !data.dry_run {
+        println!("Added validator {} to pool", vote_account);
+    }
+}
+
+fn remove_validator(data: &Data, vote_account: &Pubkey) {
+    let stake_pool = get_stake_pool(data);
+    let validator_list = get_validator_list(&data.client, &stake_pool.validator_list);
+    let validator_stake_info = validator_list
+        .find(vote_account)
+        .expect("Vote account not found in validator list");
+    let validator_seed = NonZeroU32::new(validator_stake_info.validator_seed_suffix.into());
+    let validator_stake_address = spl_stake_pool::find_stake_program_address(
+        &spl_stake_pool::id(),
+        vote_account,
+        &data.stake_pool_pubkey,
+        validator_seed,
+    )
+    .0;
+    let transient_stake_address = spl_stake_pool::find_transient_stake_program_address(
+        &spl_stake_pool::id(),
+        vote_account,
+        &data.stake_pool_pubkey,
+        validator_stake_info.transient_seed_suffix.into(),
+    )
+    .0;
+    let withdraw_authority = spl_stake_pool::find_withdraw_authority_program_address(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+    )
+    .0;
+
+    let remove_validator_instruction = spl_stake_pool::instruction::remove_validator_from_pool(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &data.payer_keypair.pubkey(),
+        &withdraw_authority,
+        &stake_pool.validator_list,
+        &validator_stake_address,
+        &transient_stake_address,
+    );
+
+    let instructions = vec![remove_validator_instruction];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    if !data.dry_run {
+        println!("Removed validator {} from pool", vote_account);
+    }
+}
+
+fn set_manager(data: &Data, new_manager: &Pubkey, new_fee_receiver: &Pubkey) {
+    let stake_pool = get_stake_pool(data);
+    if stake_pool.manager != data.payer_keypair.pubkey() {
+        eprintln!(
+            "Payer {} is not the pool manager ({}); cannot set-manager",
+            data.payer_keypair.pubkey(),
+            stake_pool.manager
+        );
+        return;
+    }
+
+    let set_manager_instruction = spl_stake_pool::instruction::set_manager(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &stake_pool.manager,
+        new_manager,
+        new_fee_receiver,
+    );
+
+    let instructions = vec![set_manager_instruction];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    if !data.dry_run {
+        println!("Set pool manager to {}", new_manager);
+    }
+}
+
+fn set_fee(data: &Data, fee_type: &SetFeeType, numerator: u64, denominator: u64) {
+    let stake_pool = get_stake_pool(data);
+    if stake_pool.manager != data.payer_keypair.pubkey() {
+        eprintln!(
+            "Payer {} is not the pool manager ({}); cannot set-fee",
+            data.payer_keypair.pubkey(),
+            stake_pool.manager
+        );
+        return;
+    }
+
+    let fee = Fee {
+        numerator,
+        denominator,
+    };
+    let fee_type = match fee_type {
+        SetFeeType::Epoch => spl_stake_pool::state::FeeType::Epoch(fee),
+        SetFeeType::StakeWithdrawal => spl_stake_pool::state::FeeType::StakeWithdrawal(fee),
+        SetFeeType::SolWithdrawal => spl_stake_pool::state::FeeType::SolWithdrawal(fee),
+        SetFeeType::StakeDeposit => spl_stake_pool::state::FeeType::StakeDeposit(fee),
+        SetFeeType::SolDeposit => spl_stake_pool::state::FeeType::SolDeposit(fee),
+    };
+
+    let set_fee_instruction = spl_stake_pool::instruction::set_fee(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &stake_pool.manager,
+        fee_type,
+    );
+
+    let instructions = vec![set_fee_instruction];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    if !data.dry_run {
+        println!("Updated pool fee");
+    }
+}
+
+fn set_staker(data: &Data, new_staker: &Pubkey) {
+    let stake_pool = get_stake_pool(data);
+    if data.payer_keypair.pubkey() != stake_pool.manager && data.payer_keypair.pubkey() != stake_pool.staker {
+        eprintln!(
+            "Payer {} is neither the pool manager ({}) nor the staker ({}); cannot set-staker",
+            data.payer_keypair.pubkey(),
+            stake_pool.manager,
+            stake_pool.staker
+        );
+        return;
+    }
+
+    let set_staker_instruction = spl_stake_pool::instruction::set_staker(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &data.payer_keypair.pubkey(),
+        new_staker,
+    );
+
+    let instructions = vec![set_staker_instruction];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    if !data.dry_run {
+        println!("Set pool staker to {}", new_staker);
+    }
+}
+
+fn list_pools(client: &RpcClient) {
+    let account_type_filter = RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+        0,
+        &[spl_stake_pool::state::AccountType::StakePool as u8],
+    ));
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![account_type_filter]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let accounts = client
+        .get_program_accounts_with_config(&spl_stake_pool::id(), config)
+        .unwrap();
+
+    println!("\n==========================================");
+    println!("Stake Pools ({})", accounts.len());
+    println!("==========================================");
+    for (pubkey, account) in accounts {
+        let mut account_data = account.data.as_slice();
+        let stake_pool = match StakePool::deserialize(&mut account_data) {
+            Ok(stake_pool) => stake_pool,
+            Err(_) => continue,
+        };
+        println!("\nStake Pool Pubkey: {}", pubkey);
+        println!("  Manager: {}", stake_pool.manager);
+        println!("  Reserve stake: {}", stake_pool.reserve_stake);
+        println!("  Mint: {}", stake_pool.pool_mint);
+        println!("  Total Staked SOL (lamports): {}", stake_pool.total_lamports);
+    }
+}
+
+fn list_validator_stake_accounts(data: &Data) {
+    let withdraw_authority = spl_stake_pool::find_withdraw_authority_program_address(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+    )
+    .0;
+
+    // Stake account Meta is laid out as: state tag (4 bytes) + rent_exempt_reserve (8 bytes)
+    // + staker (32 bytes) + withdrawer (32 bytes), so the withdrawer starts at byte 44.
+    let withdrawer_filter = RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+        44,
+        &withdraw_authority.to_bytes(),
+    ));
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![withdrawer_filter]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let accounts = data
+        .client
+        .get_program_accounts_with_config(&solana_sdk::stake::program::id(), config)
+        .unwrap();
+
+    println!("\n==========================================");
+    println!("Stake accounts controlled by the pool ({})", accounts.len());
+    println!("==========================================");
+    for (pubkey, account) in accounts {
+        println!("{} ({} lamports)", pubkey, account.lamports);
+    }
+}
+
 fn update_stake_pool(data: &Data) {
     let stake_pool = get_stake_pool(data);
     let validator_list = get_validator_list(&data.client, &stake_pool.validator_list);
@@ -196,46 +828,115 @@ fn update_stake_pool(data: &Data) {
             false,
         );
     let update_list_instructions_len = update_list_instructions.len();
-    let signers = vec![&data.payer_keypair];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
     if update_list_instructions_len > 0 {
         let last_instruction = update_list_instructions.split_off(update_list_instructions_len - 1);
         for instruction in update_list_instructions {
             send_instructions(
                 &data.client,
                 &[instruction],
-                &data.payer_keypair.pubkey(),
+                &data.fee_payer_keypair.pubkey(),
                 &signers,
                 false,
+                data.dry_run,
             );
         }
         send_instructions(
             &data.client,
             &last_instruction,
-            &data.payer_keypair.pubkey(),
+            &data.fee_payer_keypair.pubkey(),
             &signers,
             true,
+            data.dry_run,
         );
     }
 
     send_instructions(
         &data.client,
         &final_instructions,
-        &data.payer_keypair.pubkey(),
+        &data.fee_payer_keypair.pubkey(),
         &signers,
         true,
+        data.dry_run,
     );
 }
 
+fn refresh_validator_stake_info(data: &Data, vote_account: &Pubkey) -> ValidatorStakeInfo {
+    let stake_pool = get_stake_pool(data);
+    let validator_list = get_validator_list(&data.client, &stake_pool.validator_list);
+    *validator_list
+        .find(vote_account)
+        .expect("Vote account not found in validator list")
+}
+
+// Ensures the payer has enough lamports to cover `extra` (e.g. a deposit amount) and,
+// separately, that whichever keypair is actually paying transaction fees has at least the
+// rent-exempt minimum to cover them. When payer and fee payer are the same account (the
+// default, no `--fee-payer` case), both requirements draw from the same balance and must be
+// checked together. Prints a descriptive error and returns Err if not, so callers can bail
+// out before submitting a transaction that would fail late.
+fn check_fee_headroom(data: &Data, extra: u64) -> Result<(), String> {
+    let payer_account = data.client.get_account(&data.payer_keypair.pubkey()).unwrap();
+    let min_rent_exempt_balance = data
+        .client
+        .get_minimum_balance_for_rent_exemption(0)
+        .unwrap();
+    let same_account = data.fee_payer_keypair.pubkey() == data.payer_keypair.pubkey();
+
+    let required = if same_account {
+        extra + min_rent_exempt_balance
+    } else {
+        extra
+    };
+    if payer_account.lamports() < required {
+        return Err(format!(
+            "Insufficient balance: payer has {} lamports but needs at least {}{}",
+            payer_account.lamports(),
+            required,
+            if same_account {
+                format!(
+                    " ({} requested + {} rent-exempt minimum)",
+                    extra, min_rent_exempt_balance
+                )
+            } else {
+                String::new()
+            }
+        ));
+    }
+
+    if !same_account {
+        let fee_payer_account = data
+            .client
+            .get_account(&data.fee_payer_keypair.pubkey())
+            .unwrap();
+        if fee_payer_account.lamports() < min_rent_exempt_balance {
+            return Err(format!(
+                "Insufficient balance: fee payer {} has {} lamports but needs at least {} (rent-exempt minimum)",
+                data.fee_payer_keypair.pubkey(),
+                fee_payer_account.lamports(),
+                min_rent_exempt_balance
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn deposit_sol(data: &Data, amount: f64) {
     let stake_pool = get_stake_pool(data);
-    let fee_payer = data.payer_keypair.insecure_clone();
     let amount = solana_native_token::sol_to_lamports(amount);
 
-    // TODO: check balance of payer
+    if let Err(message) = check_fee_headroom(data, amount) {
+        eprintln!("{}", message);
+        return;
+    }
 
     // ephemeral SOL account just to do the transfer
     let user_sol_transfer = Keypair::new();
-    let signers = vec![&fee_payer, &user_sol_transfer, &data.payer_keypair];
+    let signers = vec![
+        &data.payer_keypair,
+        &data.fee_payer_keypair,
+        &user_sol_transfer,
+    ];
 
     let mut instructions: Vec<Instruction> = vec![];
     // Create the ephemeral SOL account
@@ -272,13 +973,188 @@ fn deposit_sol(data: &Data, amount: f64) {
     send_instructions(
         &data.client,
         &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    print_stake_pool_financials(data);
+}
+
+fn deposit_stake(data: &Data, stake_account: &Pubkey, vote_account: &Pubkey) {
+    let stake_pool = get_stake_pool(data);
+
+    let validator_stake_address = spl_stake_pool::find_stake_program_address(
+        &spl_stake_pool::id(),
+        vote_account,
+        &data.stake_pool_pubkey,
+        None,
+    )
+    .0;
+
+    let withdraw_authority = spl_stake_pool::find_withdraw_authority_program_address(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+    )
+    .0;
+
+    let pool_token_receiver_account =
+        get_associated_token_address(&data.payer_keypair.pubkey(), &stake_pool.pool_mint);
+    let referrer_token_account = pool_token_receiver_account;
+
+    // deposit_stake already bundles the staker/withdrawer re-authorization instructions
+    // (from deposit_stake_withdraw_authority to the pool's withdraw authority) ahead of the
+    // deposit CPI, so there's no need to build those by hand here.
+    let instructions = spl_stake_pool::instruction::deposit_stake(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &stake_pool.validator_list,
+        &withdraw_authority,
+        stake_account,
+        &data.payer_keypair.pubkey(),
+        &validator_stake_address,
+        &stake_pool.reserve_stake,
+        &pool_token_receiver_account,
+        &stake_pool.manager_fee_account,
+        &referrer_token_account,
+        &stake_pool.pool_mint,
+        &spl_token::id(),
+    );
+
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    print_stake_pool_financials(data);
+}
+
+fn withdraw_sol(data: &Data, amount: f64) {
+    let stake_pool = get_stake_pool(data);
+    let amount = solana_native_token::sol_to_lamports(amount);
+
+    if let Err(message) = check_fee_headroom(data, 0) {
+        eprintln!("{}", message);
+        return;
+    }
+
+    let pool_tokens_from =
+        get_associated_token_address(&data.payer_keypair.pubkey(), &stake_pool.pool_mint);
+    let withdraw_authority = spl_stake_pool::find_withdraw_authority_program_address(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+    )
+    .0;
+
+    let withdraw_instruction = spl_stake_pool::instruction::withdraw_sol(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &withdraw_authority,
         &data.payer_keypair.pubkey(),
+        &pool_tokens_from,
+        &stake_pool.reserve_stake,
+        &data.payer_keypair.pubkey(),
+        &stake_pool.manager_fee_account,
+        &stake_pool.pool_mint,
+        &spl_token::id(),
+        amount,
+    );
+
+    let instructions = vec![withdraw_instruction];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
         &signers,
         true,
+        data.dry_run,
     );
     print_stake_pool_financials(data);
 }
 
+fn withdraw_stake(data: &Data, amount: f64, vote_account: &Pubkey) {
+    let stake_pool = get_stake_pool(data);
+    let lamports = solana_native_token::sol_to_lamports(amount);
+
+    if let Err(message) = check_fee_headroom(data, 0) {
+        eprintln!("{}", message);
+        return;
+    }
+
+    let validator_list = get_validator_list(&data.client, &stake_pool.validator_list);
+    let validator_stake_info = validator_list
+        .find(vote_account)
+        .expect("Vote account not found in validator list");
+    print_validator_stake_info(validator_stake_info);
+
+    let validator_seed = NonZeroU32::new(validator_stake_info.validator_seed_suffix.into());
+    let validator_stake_address = spl_stake_pool::find_stake_program_address(
+        &spl_stake_pool::id(),
+        vote_account,
+        &data.stake_pool_pubkey,
+        validator_seed,
+    )
+    .0;
+
+    let withdraw_authority = spl_stake_pool::find_withdraw_authority_program_address(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+    )
+    .0;
+
+    // Fresh stake account to receive the split, owned by the payer
+    let user_stake_account = Keypair::new();
+    let user_stake_account_rent = data
+        .client
+        .get_minimum_balance_for_rent_exemption(solana_sdk::stake::state::StakeStateV2::size_of())
+        .unwrap();
+    let pool_tokens_from =
+        get_associated_token_address(&data.payer_keypair.pubkey(), &stake_pool.pool_mint);
+
+    let create_user_stake_account_instruction = system_instruction::create_account(
+        &data.payer_keypair.pubkey(),
+        &user_stake_account.pubkey(),
+        user_stake_account_rent,
+        solana_sdk::stake::state::StakeStateV2::size_of() as u64,
+        &solana_sdk::stake::program::id(),
+    );
+
+    let withdraw_instruction = spl_stake_pool::instruction::withdraw_stake(
+        &spl_stake_pool::id(),
+        &data.stake_pool_pubkey,
+        &stake_pool.validator_list,
+        &withdraw_authority,
+        &validator_stake_address,
+        &user_stake_account.pubkey(),
+        &data.payer_keypair.pubkey(),
+        &data.payer_keypair.pubkey(),
+        &pool_tokens_from,
+        &stake_pool.manager_fee_account,
+        &stake_pool.pool_mint,
+        &spl_token::id(),
+        lamports,
+    );
+
+    let instructions = vec![create_user_stake_account_instruction, withdraw_instruction];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair, &user_stake_account];
+    send_instructions(
+        &data.client,
+        &instructions,
+        &data.fee_payer_keypair.pubkey(),
+        &signers,
+        true,
+        data.dry_run,
+    );
+    let validator_stake_info = refresh_validator_stake_info(data, vote_account);
+    print_validator_stake_info(&validator_stake_info);
+    print_stake_pool_financials(data);
+}
+
 fn print_validator_stake_info(validator_stake_info: &ValidatorStakeInfo) {
     let active_stake_lamports: u64 = validator_stake_info.active_stake_lamports.into();
     let transient_stake_lamports: u64 = validator_stake_info.transient_stake_lamports.into();
@@ -317,16 +1193,17 @@ fn increase_validator_stake_with_vote(data: &Data, amount: f64, validator_addres
             0,
         );
     let instructions = vec![increase_validator_stake_with_vote_instruction];
-    let signers = vec![&data.payer_keypair, &data.payer_keypair];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
     send_instructions(
         &data.client,
         &instructions,
-        &data.payer_keypair.pubkey(),
+        &data.fee_payer_keypair.pubkey(),
         &signers,
         true,
+        data.dry_run,
     );
-    // TODO: Update data before printing
-    print_validator_stake_info(validator_stake_info);
+    let validator_stake_info = refresh_validator_stake_info(data, vote_account);
+    print_validator_stake_info(&validator_stake_info);
 }
 
 fn decrease_validator_stake_with_vote(data: &Data, amount: f64, validator_address: &Pubkey) {
@@ -352,14 +1229,15 @@ fn decrease_validator_stake_with_vote(data: &Data, amount: f64, validator_addres
         );
 
     let instructions = vec![decrease_validator_stake_with_vote_instruction];
-    let signers = vec![&data.payer_keypair, &data.payer_keypair];
+    let signers = vec![&data.payer_keypair, &data.fee_payer_keypair];
     send_instructions(
         &data.client,
         &instructions,
-        &data.payer_keypair.pubkey(),
+        &data.fee_payer_keypair.pubkey(),
         &signers,
         true,
+        data.dry_run,
     );
-    // TODO: Update data before printing
-    print_validator_stake_info(validator_stake_info);
+    let validator_stake_info = refresh_validator_stake_info(data, vote_account);
+    print_validator_stake_info(&validator_stake_info);
 }